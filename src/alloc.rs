@@ -1,10 +1,21 @@
+use allocator_api2::alloc::Allocator;
+use allocator_api2::alloc::Global;
 use std::alloc::Layout;
 use std::ptr::NonNull;
 
-/// Allocate and handle allocation errors.
+/// Allocate from the global allocator and handle allocation errors.
 ///
 /// SAFETY: `layout` must have non-zero size
 pub(crate) unsafe fn alloc(layout: Layout) -> NonNull<u8> {
-    let ptr: *mut u8 = unsafe { std::alloc::alloc(layout) };
-    NonNull::new(ptr).unwrap_or_else(|| std::alloc::handle_alloc_error(layout))
+    unsafe { alloc_in(&Global, layout) }
+}
+
+/// Allocate from `allocator` and handle allocation errors.
+///
+/// SAFETY: `layout` must have non-zero size
+pub(crate) unsafe fn alloc_in<A: Allocator>(allocator: &A, layout: Layout) -> NonNull<u8> {
+    match allocator.allocate(layout) {
+        Ok(ptr) => ptr.cast(),
+        Err(_) => std::alloc::handle_alloc_error(layout),
+    }
 }
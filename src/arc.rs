@@ -0,0 +1,220 @@
+//! Like `std::sync::Arc` but:
+//!
+//! * Does not have weak references
+//! * Supports dynamically-sized conversion to `Arc<WithHash<str>>` or `Arc<WithHash<[T]>>`
+
+use crate::hash;
+use crate::WithHash;
+use std::alloc::Layout;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+/// Past this refcount we abort rather than risk an overflow back to zero.
+/// Matches the threshold used by `std::sync::Arc`.
+const MAX_REFCOUNT: usize = isize::MAX as usize;
+
+#[repr(C)]
+struct ArcBox<T: ?Sized> {
+    refcount: AtomicUsize,
+    value: T,
+}
+
+fn arcbox_layout_and_value_offset(value_layout: Layout) -> (Layout, usize) {
+    let (layout, value_offset) = Layout::new::<AtomicUsize>().extend(value_layout).unwrap();
+    (layout.pad_to_align(), value_offset)
+}
+
+pub struct Arc<T: ?Sized> {
+    ptr: NonNull<ArcBox<T>>,
+    phantom: PhantomData<ArcBox<T>>,
+}
+
+unsafe impl<T: ?Sized + Send + Sync> Send for Arc<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for Arc<T> {}
+
+impl<T: ?Sized> Arc<T> {
+    #[inline(always)]
+    fn inner(&self) -> &ArcBox<T> {
+        // SAFETY: While this Arc is alive we’re guaranteed that the inner pointer is valid.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+// Implicit `T: Sized`
+impl<T> Arc<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            ptr: Box::leak(Box::new(ArcBox {
+                refcount: AtomicUsize::new(1),
+                value,
+            }))
+            .into(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for Arc<T> {
+    fn drop(&mut self) {
+        // Same atomic refcount protocol as `std::sync::Arc`: a `Release` decrement
+        // pairs with an `Acquire` fence (taken only on the thread that drops the
+        // last reference) so that all other threads’ writes through this `Arc`
+        // happen-before the `drop_in_place`/`dealloc` below.
+        if self.inner().refcount.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        std::sync::atomic::fence(Ordering::Acquire);
+        unsafe {
+            let layout = Layout::for_value(self.ptr.as_ref());
+            std::ptr::drop_in_place(&mut self.ptr.as_mut().value);
+            std::alloc::dealloc(self.ptr.cast().as_ptr(), layout);
+        }
+    }
+}
+
+impl<T: ?Sized> Clone for Arc<T> {
+    fn clone(&self) -> Self {
+        // `Relaxed` is enough here: the increment only needs to be atomic,
+        // not synchronized with anything else, because holding a live `Arc`
+        // already proves the value is not being torn down.
+        let old_count = self.inner().refcount.fetch_add(1, Ordering::Relaxed);
+        if old_count > MAX_REFCOUNT {
+            std::process::abort();
+        }
+        Self {
+            ptr: self.ptr,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> std::ops::Deref for Arc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner().value
+    }
+}
+
+impl<T: ?Sized> AsRef<T> for Arc<T> {
+    fn as_ref(&self) -> &T {
+        &self.inner().value
+    }
+}
+
+// Implicit `T: Sized`
+impl<T> From<T> for Arc<T> {
+    fn from(value: T) -> Self {
+        Arc::new(value)
+    }
+}
+
+fn new_dynamically_sized_arcbox(value_layout: Layout) -> (NonNull<u8>, usize) {
+    let (layout, arcvalue_offset) = arcbox_layout_and_value_offset(value_layout);
+    unsafe {
+        let arcbox_ptr: NonNull<u8> = crate::alloc::alloc(layout);
+
+        let refcount_ptr = arcbox_ptr.cast::<AtomicUsize>();
+        refcount_ptr.as_ptr().write(AtomicUsize::new(1));
+
+        (arcbox_ptr, arcvalue_offset)
+    }
+}
+
+impl<T> ArcBox<WithHash<[T]>> {
+    fn new_withhash_slice(input_slice: &[T]) -> NonNull<Self>
+    where
+        T: Copy + Hash,
+    {
+        Self::new_withhash_slice_with_hash(hash(input_slice), input_slice)
+    }
+
+    /// Like [`Self::new_withhash_slice`], but writes a caller-provided hash instead of
+    /// hashing `input_slice` itself. See [`WithHash::initialize_with_hash`] for why that
+    /// matters for `str`.
+    fn new_withhash_slice_with_hash(hash_value: u64, input_slice: &[T]) -> NonNull<Self>
+    where
+        T: Copy,
+    {
+        let (withhash_layout, slice_offset) = WithHash::slice_layout_and_value_offset(input_slice);
+        let (arcbox_ptr, arcvalue_offset) = new_dynamically_sized_arcbox(withhash_layout);
+        unsafe {
+            let withhash_ptr = arcbox_ptr.as_ptr().add(arcvalue_offset);
+            WithHash::initialize_with_hash(withhash_ptr, slice_offset, hash_value, input_slice);
+
+            // TODO: use `ptr::from_raw_parts_mut` when available (https://github.com/rust-lang/rust/issues/81513)
+
+            // Until then, `slice_from_raw_parts_mut` returns a raw wide pointer with the wrong type
+            // but the correct components (data pointer and length metadata).
+            let raw_slice = core::ptr::slice_from_raw_parts_mut::<T>(
+                arcbox_ptr.as_ptr().cast::<T>(),
+                input_slice.len(),
+            );
+            // This cast preserves both pointer components.
+            NonNull::new_unchecked(raw_slice as *mut ArcBox<WithHash<[T]>>)
+        }
+    }
+}
+
+impl<T: Copy + Hash> From<&'_ [T]> for Arc<WithHash<[T]>> {
+    fn from(input_slice: &'_ [T]) -> Self {
+        Self {
+            ptr: ArcBox::new_withhash_slice(input_slice),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl From<&'_ str> for Arc<WithHash<str>> {
+    fn from(value: &'_ str) -> Self {
+        // Hash `value` itself, not `value.as_bytes()`: `str`'s `Hash` impl appends a
+        // terminator byte that `[u8]`'s `Hash` impl does not, so the two disagree.
+        let bytes_ptr: NonNull<ArcBox<WithHash<[u8]>>> =
+            ArcBox::new_withhash_slice_with_hash(hash(value), value.as_bytes());
+
+        // The wide pointer metadata is compatible between `*ArcBox<WithHash<[u8]>>`
+        // and `*ArcBox<WithHash<str>>` (the length as a `usize` counting bytes)
+        let ptr = bytes_ptr.as_ptr() as *mut ArcBox<WithHash<str>>;
+
+        Self {
+            // SAFETY: points to a fully initialized allocation with the appropriate layout
+            ptr: unsafe { NonNull::new_unchecked(ptr) },
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Arc;
+    use crate::PreHash;
+    use crate::WithHash;
+
+    #[test]
+    fn new_deref_clone_and_drop() {
+        let a = Arc::new(42);
+        let b = a.clone();
+        assert_eq!(*a, 42);
+        assert_eq!(*b, 42);
+        drop(a);
+        assert_eq!(*b, 42);
+    }
+
+    #[test]
+    fn from_slice_preserves_contents_and_hash() {
+        let slice: &[i32] = &[1, 2, 3];
+        let arc: Arc<WithHash<[i32]>> = Arc::from(slice);
+        assert_eq!(&**arc, slice);
+        assert_eq!(PreHash::precomputed_hash(&*arc), crate::hash(slice));
+    }
+
+    #[test]
+    fn from_str_preserves_contents_and_hash() {
+        let arc: Arc<WithHash<str>> = Arc::from("hello");
+        assert_eq!(&**arc, "hello");
+        assert_eq!(PreHash::precomputed_hash(&*arc), crate::hash("hello"));
+    }
+}
@@ -0,0 +1,126 @@
+//! String/value interners built on [`PreHashMap`] and [`Rc`].
+//!
+//! Interning deduplicates immutable values into shared, prehashed handles:
+//! each handle stores its hash, and repeated interning of an equal value
+//! returns a cheap clone of the same [`Rc`]. Every call hashes its argument
+//! once to probe the map; on a miss, building the new handle hashes it a
+//! second time.
+
+use crate::hash;
+use crate::PreHash;
+use crate::PreHashMap;
+use crate::Rc;
+use crate::WithHash;
+use std::cell::RefCell;
+use std::hash::Hash;
+
+/// A probe key that carries a precomputed hash without allocating,
+/// so a lookup by borrowed data never has to build an owned `Rc` first.
+struct Probe<'a, T: ?Sized> {
+    hash: u64,
+    value: &'a T,
+}
+
+impl<T: ?Sized> PreHash for Probe<'_, T> {
+    type Hashed = T;
+
+    fn precomputed_hash(self_: &Self) -> u64 {
+        self_.hash
+    }
+
+    fn hashed_value(self_: &Self) -> &Self::Hashed {
+        self_.value
+    }
+}
+
+/// Deduplicates immutable strings into shared [`Rc<WithHash<str>>`] handles.
+pub struct Interner {
+    strings: RefCell<PreHashMap<Rc<WithHash<str>>, ()>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self {
+            strings: RefCell::new(PreHashMap::new()),
+        }
+    }
+
+    /// Returns a handle for `s`, reusing a previously interned one if `s` was seen before.
+    pub fn intern(&self, s: &str) -> Rc<WithHash<str>> {
+        let probe = Probe {
+            hash: hash(s),
+            value: s,
+        };
+        if let Some(existing) = self.strings.borrow().get_key_value(&probe) {
+            return existing.0.clone();
+        }
+        let interned: Rc<WithHash<str>> = Rc::from(s);
+        self.strings.borrow_mut().insert(interned.clone(), ());
+        interned
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Deduplicates immutable slices into shared [`Rc<WithHash<[T]>>`] handles.
+pub struct SliceInterner<T: Copy + Hash + Eq> {
+    slices: RefCell<PreHashMap<Rc<WithHash<[T]>>, ()>>,
+}
+
+impl<T: Copy + Hash + Eq> SliceInterner<T> {
+    pub fn new() -> Self {
+        Self {
+            slices: RefCell::new(PreHashMap::new()),
+        }
+    }
+
+    /// Returns a handle for `slice`, reusing a previously interned one if `slice` was seen before.
+    pub fn intern_slice(&self, slice: &[T]) -> Rc<WithHash<[T]>> {
+        let probe = Probe {
+            hash: hash(slice),
+            value: slice,
+        };
+        if let Some(existing) = self.slices.borrow().get_key_value(&probe) {
+            return existing.0.clone();
+        }
+        let interned: Rc<WithHash<[T]>> = Rc::from(slice);
+        self.slices.borrow_mut().insert(interned.clone(), ());
+        interned
+    }
+}
+
+impl<T: Copy + Hash + Eq> Default for SliceInterner<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interner;
+    use super::SliceInterner;
+
+    #[test]
+    fn intern_dedupes_equal_strings() {
+        let interner = Interner::new();
+        let a = interner.intern("hello");
+        let b = interner.intern("hello");
+        assert!(std::ptr::eq(&*a, &*b));
+        let c = interner.intern("world");
+        assert!(!std::ptr::eq(&*a, &*c));
+    }
+
+    #[test]
+    fn intern_slice_dedupes_equal_slices() {
+        let interner = SliceInterner::new();
+        let a = interner.intern_slice(&[1, 2, 3]);
+        let b = interner.intern_slice(&[1, 2, 3]);
+        assert!(std::ptr::eq(&*a, &*b));
+        let c = interner.intern_slice(&[4, 5]);
+        assert!(!std::ptr::eq(&*a, &*c));
+    }
+}
@@ -5,14 +5,31 @@ use std::hash::Hasher;
 use std::sync::OnceLock;
 
 mod alloc;
+mod arc;
+mod intern;
 mod map;
 mod rc;
+mod set;
 mod with_hash;
 
+pub use self::arc::Arc;
+pub use self::intern::Interner;
+pub use self::intern::SliceInterner;
 pub use self::map::PreHashMap;
 pub use self::rc::Rc;
+pub use self::set::PreHashSet;
 pub use self::with_hash::WithHash;
 
+#[cfg(feature = "rkyv")]
+pub use self::map::ArchivedPreHashMap;
+#[cfg(feature = "rkyv")]
+pub use self::map::PreHashMapResolver;
+#[cfg(feature = "rkyv")]
+pub use self::with_hash::ArchivedWithHash;
+
+#[cfg(feature = "rayon")]
+pub use self::map::rayon;
+
 /// Computes and returns the hash of `value`,
 /// with a hasher configured randomly once per process.
 ///
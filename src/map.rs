@@ -1,17 +1,33 @@
 use crate::PreHash;
+use allocator_api2::alloc::Allocator;
+use allocator_api2::alloc::Global;
 use hashbrown::hash_map::RawEntryMut;
 use hashbrown::Equivalent;
 use hashbrown::HashMap;
+#[cfg(feature = "rkyv")]
+use rkyv::out_field;
+#[cfg(feature = "rkyv")]
+use rkyv::vec::ArchivedVec;
+#[cfg(feature = "rkyv")]
+use rkyv::Archive;
+#[cfg(feature = "rkyv")]
+use rkyv::Archived;
+#[cfg(feature = "rkyv")]
+use rkyv::Deserialize as RkyvDeserialize;
+#[cfg(feature = "rkyv")]
+use rkyv::Fallible;
+#[cfg(feature = "rkyv")]
+use rkyv::Serialize as RkyvSerialize;
 
-pub struct PreHashMap<K, V>
+pub struct PreHashMap<K, V, A: Allocator = Global>
 where
     K: PreHash,
 {
-    hashbrown: HashMap<K, V, NotAHasher>,
+    hashbrown: HashMap<K, V, NotAHasher, A>,
 }
 
 /// This does *not* implement `BuildHasher`. We never want Hashbrown to do the hashing.
-struct NotAHasher;
+pub(crate) struct NotAHasher;
 
 impl<K, V> PreHashMap<K, V>
 where
@@ -19,8 +35,28 @@ where
     K::Hashed: Eq,
 {
     pub fn new() -> Self {
+        Self::new_in(Global)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_in(capacity, Global)
+    }
+}
+
+impl<K, V, A: Allocator> PreHashMap<K, V, A>
+where
+    K: PreHash,
+    K::Hashed: Eq,
+{
+    pub fn new_in(allocator: A) -> Self {
         Self {
-            hashbrown: HashMap::with_hasher(NotAHasher),
+            hashbrown: HashMap::with_hasher_in(NotAHasher, allocator),
+        }
+    }
+
+    pub fn with_capacity_in(capacity: usize, allocator: A) -> Self {
+        Self {
+            hashbrown: HashMap::with_capacity_and_hasher_in(capacity, NotAHasher, allocator),
         }
     }
 
@@ -35,7 +71,7 @@ where
         })
     }
 
-    fn raw_entry_mut<Q>(&mut self, key: &Q) -> RawEntryMut<'_, K, V, NotAHasher>
+    fn raw_entry_mut<Q>(&mut self, key: &Q) -> RawEntryMut<'_, K, V, NotAHasher, A>
     where
         Q: PreHash,
         Q::Hashed: Equivalent<K::Hashed>,
@@ -54,6 +90,14 @@ where
         self.raw_entry(key).map(|(_key, value)| value)
     }
 
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: PreHash,
+        Q::Hashed: Equivalent<K::Hashed>,
+    {
+        self.raw_entry(key)
+    }
+
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
         match self.raw_entry_mut(&key) {
             RawEntryMut::Occupied(mut entry) => Some(entry.insert(value)),
@@ -64,4 +108,399 @@ where
             }
         }
     }
+
+    /// Inserts `key`/`value` without probing for an existing entry first.
+    ///
+    /// Callers must already know `key` is not present, for example when
+    /// building a map from a source that has already been deduplicated.
+    /// Inserting a key that is already present leaves both entries in the
+    /// map instead of replacing one, so future lookups for that key become
+    /// unreliable.
+    pub fn insert_unique_unchecked(&mut self, key: K, value: V) {
+        let hash = PreHash::precomputed_hash(&key);
+        self.hashbrown
+            .raw_table_mut()
+            .insert(hash, (key, value), |(candidate, _value)| {
+                PreHash::precomputed_hash(candidate)
+            });
+    }
+
+    pub fn iter(&self) -> hashbrown::hash_map::Iter<'_, K, V> {
+        self.hashbrown.iter()
+    }
+
+    /// Yields `(&K, &mut V)`, never `&mut K`: see the invariant on
+    /// [`WithHash::value`][crate::with_hash::WithHash] that this crate never hands out a
+    /// mutable reference to a hashed value, since mutating it in place would invalidate
+    /// its precomputed hash.
+    pub fn iter_mut(&mut self) -> hashbrown::hash_map::IterMut<'_, K, V> {
+        self.hashbrown.iter_mut()
+    }
+
+    pub fn keys(&self) -> hashbrown::hash_map::Keys<'_, K, V> {
+        self.hashbrown.keys()
+    }
+
+    pub fn values(&self) -> hashbrown::hash_map::Values<'_, K, V> {
+        self.hashbrown.values()
+    }
+
+    pub fn values_mut(&mut self) -> hashbrown::hash_map::ValuesMut<'_, K, V> {
+        self.hashbrown.values_mut()
+    }
+
+    pub fn drain(&mut self) -> hashbrown::hash_map::Drain<'_, K, V, A> {
+        self.hashbrown.drain()
+    }
+
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.hashbrown.retain(f)
+    }
+}
+
+impl<K, V, A: Allocator> IntoIterator for PreHashMap<K, V, A>
+where
+    K: PreHash,
+{
+    type Item = (K, V);
+    type IntoIter = hashbrown::hash_map::IntoIter<K, V, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.hashbrown.into_iter()
+    }
+}
+
+impl<'a, K, V, A: Allocator> IntoIterator for &'a PreHashMap<K, V, A>
+where
+    K: PreHash,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = hashbrown::hash_map::Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.hashbrown.iter()
+    }
+}
+
+impl<'a, K, V, A: Allocator> IntoIterator for &'a mut PreHashMap<K, V, A>
+where
+    K: PreHash,
+{
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = hashbrown::hash_map::IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.hashbrown.iter_mut()
+    }
+}
+
+/// Parallel iterators over [`PreHashMap`], mirroring hashbrown's own
+/// `external_trait_impls::rayon` support for `HashMap`.
+///
+/// These are plain inherent methods rather than impls of rayon's
+/// `IntoParallelIterator` trait: that trait's associated `Iter` type would have
+/// to name `HashMap<K, V, NotAHasher, A>`'s own iterator type, which leaks the
+/// private `NotAHasher` marker into a public interface. Calling into
+/// hashbrown's trait impls by method syntax and returning `impl ParallelIterator`
+/// sidesteps that without giving up anything callers need.
+#[cfg(feature = "rayon")]
+pub mod rayon {
+    use crate::PreHash;
+    use crate::PreHashMap;
+    use allocator_api2::alloc::Allocator;
+    use ::rayon::iter::IntoParallelIterator;
+    use ::rayon::iter::ParallelIterator;
+
+    impl<K, V, A: Allocator> PreHashMap<K, V, A>
+    where
+        K: PreHash,
+    {
+        /// Because keys carry their own precomputed hash, a parallel consumer
+        /// that re-inserts entries into a new `PreHashMap` (for example during
+        /// a merge/reduce step) can reuse it via
+        /// [`insert_unique_unchecked`][PreHashMap::insert_unique_unchecked]
+        /// instead of rehashing.
+        pub fn par_iter(&self) -> impl ParallelIterator<Item = (&K, &V)>
+        where
+            K: Sync,
+            V: Sync,
+        {
+            (&self.hashbrown).into_par_iter()
+        }
+
+        pub fn par_values_mut(&mut self) -> impl ParallelIterator<Item = &mut V>
+        where
+            K: Send + Sync,
+            V: Send,
+        {
+            self.hashbrown.par_values_mut()
+        }
+
+        pub fn into_par_iter(self) -> impl ParallelIterator<Item = (K, V)>
+        where
+            K: Send,
+            V: Send,
+            A: Send,
+        {
+            self.hashbrown.into_par_iter()
+        }
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for PreHashMap<K, V>
+where
+    K: PreHash,
+    K::Hashed: Eq,
+{
+    /// Builds a map from an iterator of already-distinct keys, via
+    /// [`insert_unique_unchecked`][Self::insert_unique_unchecked].
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut map = Self::with_capacity(iter.size_hint().0);
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, A: Allocator> Extend<(K, V)> for PreHashMap<K, V, A>
+where
+    K: PreHash,
+    K::Hashed: Eq,
+{
+    /// Reserves once for the iterator's lower bound, then inserts every pair
+    /// via [`insert_unique_unchecked`][Self::insert_unique_unchecked]. Callers
+    /// must already know the new keys are distinct from each other and from
+    /// the map's existing keys.
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.hashbrown
+            .raw_table_mut()
+            .reserve(iter.size_hint().0, |(candidate, _value)| {
+                PreHash::precomputed_hash(candidate)
+            });
+        for (key, value) in iter {
+            self.insert_unique_unchecked(key, value);
+        }
+    }
+}
+
+/// Borrowed view of one entry, used only to drive [`ArchivedVec::serialize_from_iter`]
+/// without collecting owned `(K, V)` pairs.
+#[cfg(feature = "rkyv")]
+struct Entry<'a, K, V> {
+    key: &'a K,
+    value: &'a V,
+}
+
+#[cfg(feature = "rkyv")]
+impl<K: Archive, V: Archive> Archive for Entry<'_, K, V> {
+    type Archived = (Archived<K>, Archived<V>);
+    type Resolver = (K::Resolver, V::Resolver);
+
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        let (fp, fo) = out_field!(out.0);
+        self.key.resolve(pos + fp, resolver.0, fo);
+        let (fp, fo) = out_field!(out.1);
+        self.value.resolve(pos + fp, resolver.1, fo);
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<K, V, S> RkyvSerialize<S> for Entry<'_, K, V>
+where
+    K: RkyvSerialize<S>,
+    V: RkyvSerialize<S>,
+    S: Fallible + ?Sized,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok((
+            self.key.serialize(serializer)?,
+            self.value.serialize(serializer)?,
+        ))
+    }
+}
+
+/// Archived form of [`PreHashMap`]: a flat list of entries. Each key is an
+/// archived [`crate::WithHash`][crate::with_hash::ArchivedWithHash]-like type
+/// that keeps its hash inline, so rebuilding the map on load (see the
+/// `Deserialize` impl below) never calls `Hash`.
+///
+/// # Caveat
+///
+/// The hashes stored in this archive come from [`crate::hash`], which seeds
+/// a `RandomState` once per process (`SHARED_RANDOM`). They are only
+/// guaranteed to agree with a `PreHashMap` rebuilt in another process if that
+/// process used the same seed. Deserializing an archive written by a
+/// different process must either re-hash every key or persist and restore
+/// the seed out of band.
+#[cfg(feature = "rkyv")]
+pub struct ArchivedPreHashMap<K: Archive, V: Archive> {
+    entries: ArchivedVec<(Archived<K>, Archived<V>)>,
+}
+
+#[cfg(feature = "rkyv")]
+pub struct PreHashMapResolver {
+    entries: rkyv::vec::VecResolver,
+}
+
+#[cfg(feature = "rkyv")]
+impl<K, V> Archive for PreHashMap<K, V>
+where
+    K: PreHash + Archive,
+    K::Hashed: Eq,
+    V: Archive,
+{
+    type Archived = ArchivedPreHashMap<K, V>;
+    type Resolver = PreHashMapResolver;
+
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        let (fp, fo) = out_field!(out.entries);
+        ArchivedVec::resolve_from_len(self.hashbrown.len(), pos + fp, resolver.entries, fo);
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<K, V, S> RkyvSerialize<S> for PreHashMap<K, V>
+where
+    K: PreHash + RkyvSerialize<S>,
+    K::Hashed: Eq,
+    V: RkyvSerialize<S>,
+    S: rkyv::ser::ScratchSpace + rkyv::ser::Serializer + ?Sized,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        let entries: Vec<Entry<'_, K, V>> = self
+            .hashbrown
+            .iter()
+            .map(|(key, value)| Entry { key, value })
+            .collect();
+        Ok(PreHashMapResolver {
+            entries: ArchivedVec::serialize_from_iter::<Entry<'_, K, V>, _, _, _>(
+                entries.iter(),
+                serializer,
+            )?,
+        })
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<K, V, D> RkyvDeserialize<PreHashMap<K, V>, D> for ArchivedPreHashMap<K, V>
+where
+    K: PreHash + Archive,
+    K::Hashed: Eq,
+    V: Archive,
+    Archived<K>: RkyvDeserialize<K, D>,
+    Archived<V>: RkyvDeserialize<V, D>,
+    D: Fallible + ?Sized,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<PreHashMap<K, V>, D::Error> {
+        let mut map = PreHashMap::new();
+        for (key, value) in self.entries.iter() {
+            // `insert` already goes through `raw_entry_mut`/`insert_with_hasher`
+            // using `K`'s precomputed hash, so no call to `Hash` happens here.
+            map.insert(key.deserialize(deserializer)?, value.deserialize(deserializer)?);
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PreHashMap;
+    use crate::WithHash;
+
+    #[test]
+    fn insert_unique_unchecked_is_visible_via_get() {
+        let mut map = PreHashMap::new();
+        map.insert_unique_unchecked(WithHash::from(1), "one");
+        map.insert_unique_unchecked(WithHash::from(2), "two");
+        assert_eq!(map.get(&WithHash::from(1)), Some(&"one"));
+        assert_eq!(map.get(&WithHash::from(2)), Some(&"two"));
+    }
+
+    #[test]
+    fn from_iter_collects_distinct_keys() {
+        let map: PreHashMap<WithHash<i32>, i32> =
+            [(WithHash::from(1), 10), (WithHash::from(2), 20)]
+                .into_iter()
+                .collect();
+        assert_eq!(map.get(&WithHash::from(1)), Some(&10));
+        assert_eq!(map.get(&WithHash::from(2)), Some(&20));
+    }
+
+    #[test]
+    fn extend_adds_new_entries() {
+        let mut map: PreHashMap<WithHash<i32>, i32> = PreHashMap::new();
+        map.insert(WithHash::from(1), 10);
+        map.extend([(WithHash::from(2), 20), (WithHash::from(3), 30)]);
+        assert_eq!(map.get(&WithHash::from(1)), Some(&10));
+        assert_eq!(map.get(&WithHash::from(2)), Some(&20));
+        assert_eq!(map.get(&WithHash::from(3)), Some(&30));
+    }
+
+    #[test]
+    fn iteration_visits_every_entry() {
+        let mut map: PreHashMap<WithHash<i32>, i32> = PreHashMap::new();
+        map.insert(WithHash::from(1), 10);
+        map.insert(WithHash::from(2), 20);
+
+        let mut values: Vec<i32> = map.values().copied().collect();
+        values.sort_unstable();
+        assert_eq!(values, [10, 20]);
+
+        for value in map.values_mut() {
+            *value += 1;
+        }
+        let mut values: Vec<i32> = (&map).into_iter().map(|(_key, value)| *value).collect();
+        values.sort_unstable();
+        assert_eq!(values, [11, 21]);
+    }
+}
+
+#[cfg(all(test, feature = "rayon"))]
+mod rayon_tests {
+    use super::PreHashMap;
+    use crate::WithHash;
+    use rayon::iter::ParallelIterator;
+
+    #[test]
+    fn par_iter_and_par_values_mut_visit_every_entry() {
+        let mut map: PreHashMap<WithHash<i32>, i32> = PreHashMap::new();
+        map.insert(WithHash::from(1), 10);
+        map.insert(WithHash::from(2), 20);
+
+        let mut values: Vec<i32> = map.par_iter().map(|(_key, value)| *value).collect();
+        values.sort_unstable();
+        assert_eq!(values, [10, 20]);
+
+        map.par_values_mut().for_each(|value| *value += 1);
+        let mut values: Vec<i32> = map.into_par_iter().map(|(_key, value)| value).collect();
+        values.sort_unstable();
+        assert_eq!(values, [11, 21]);
+    }
+}
+
+#[cfg(all(test, feature = "rkyv"))]
+mod rkyv_tests {
+    use super::PreHashMap;
+    use crate::WithHash;
+    use rkyv::Deserialize as RkyvDeserialize;
+
+    #[test]
+    fn roundtrip_preserves_entries() {
+        let mut map: PreHashMap<WithHash<u32>, u32> = PreHashMap::new();
+        map.insert(WithHash::from(1), 10);
+        map.insert(WithHash::from(2), 20);
+
+        let bytes = rkyv::to_bytes::<_, 256>(&map).unwrap();
+        let archived =
+            unsafe { rkyv::archived_root::<PreHashMap<WithHash<u32>, u32>>(&bytes) };
+        let deserialized: PreHashMap<WithHash<u32>, u32> =
+            archived.deserialize(&mut rkyv::Infallible).unwrap();
+
+        assert_eq!(deserialized.get(&WithHash::from(1)), Some(&10));
+        assert_eq!(deserialized.get(&WithHash::from(2)), Some(&20));
+    }
 }
@@ -2,8 +2,12 @@
 //!
 //! * Does not have weak references
 //! * Supports dynamically-sized conversion to `Rc<WithHash<str>>` or `Rc<WithHash<[T]>>`
+//! * Can draw its backing allocation from a custom `A: Allocator`
 
+use crate::hash;
 use crate::WithHash;
+use allocator_api2::alloc::Allocator;
+use allocator_api2::alloc::Global;
 use std::alloc::Layout;
 use std::cell::Cell;
 use std::hash::Hash;
@@ -21,15 +25,16 @@ fn rcbox_layout_and_value_offset(value_layout: Layout) -> (Layout, usize) {
     (layout.pad_to_align(), value_offset)
 }
 
-pub struct Rc<T: ?Sized> {
+pub struct Rc<T: ?Sized, A: Allocator = Global> {
     ptr: NonNull<RcBox<T>>,
+    allocator: A,
     phantom: PhantomData<RcBox<T>>,
 }
 
-impl<T: ?Sized> Rc<T> {
+impl<T: ?Sized, A: Allocator> Rc<T, A> {
     #[inline(always)]
     fn inner(&self) -> &RcBox<T> {
-        // SAFETY: While this Rc is alive weâ€™re guaranteed that the inner pointer is valid.
+        // SAFETY: While this Rc is alive we’re guaranteed that the inner pointer is valid.
         unsafe { self.ptr.as_ref() }
     }
 }
@@ -37,18 +42,28 @@ impl<T: ?Sized> Rc<T> {
 // Implicit `T: Sized`
 impl<T> Rc<T> {
     pub fn new(value: T) -> Self {
-        Self {
-            ptr: Box::leak(Box::new(RcBox {
-                refcount: Cell::new(1),
-                value,
-            }))
-            .into(),
-            phantom: PhantomData,
+        Self::new_in(value, Global)
+    }
+}
+
+// Implicit `T: Sized`
+impl<T, A: Allocator> Rc<T, A> {
+    pub fn new_in(value: T, allocator: A) -> Self {
+        let (layout, value_offset) = rcbox_layout_and_value_offset(Layout::new::<T>());
+        unsafe {
+            let rcbox_ptr = crate::alloc::alloc_in(&allocator, layout);
+            rcbox_ptr.cast::<Cell<usize>>().as_ptr().write(Cell::new(1));
+            rcbox_ptr.as_ptr().add(value_offset).cast::<T>().write(value);
+            Self {
+                ptr: NonNull::new_unchecked(rcbox_ptr.as_ptr().cast::<RcBox<T>>()),
+                allocator,
+                phantom: PhantomData,
+            }
         }
     }
 }
 
-impl<T: ?Sized> Drop for Rc<T> {
+impl<T: ?Sized, A: Allocator> Drop for Rc<T, A> {
     fn drop(&mut self) {
         let new_count = self.inner().refcount.get() - 1;
         if new_count != 0 {
@@ -57,24 +72,25 @@ impl<T: ?Sized> Drop for Rc<T> {
             unsafe {
                 let layout = Layout::for_value(self.ptr.as_ref());
                 std::ptr::drop_in_place(&mut self.ptr.as_mut().value);
-                std::alloc::dealloc(self.ptr.cast().as_ptr(), layout);
+                self.allocator.deallocate(self.ptr.cast(), layout);
             }
         }
     }
 }
 
-impl<T: ?Sized> Clone for Rc<T> {
+impl<T: ?Sized, A: Allocator + Clone> Clone for Rc<T, A> {
     fn clone(&self) -> Self {
         let new_count = self.inner().refcount.get().checked_add(1).unwrap();
         self.inner().refcount.set(new_count);
         Self {
             ptr: self.ptr,
+            allocator: self.allocator.clone(),
             phantom: PhantomData,
         }
     }
 }
 
-impl<T: ?Sized> std::ops::Deref for Rc<T> {
+impl<T: ?Sized, A: Allocator> std::ops::Deref for Rc<T, A> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -82,7 +98,7 @@ impl<T: ?Sized> std::ops::Deref for Rc<T> {
     }
 }
 
-impl<T: ?Sized> AsRef<T> for Rc<T> {
+impl<T: ?Sized, A: Allocator> AsRef<T> for Rc<T, A> {
     fn as_ref(&self) -> &T {
         &self.inner().value
     }
@@ -95,10 +111,13 @@ impl<T> From<T> for Rc<T> {
     }
 }
 
-fn new_dynamically_sized_rcbox(value_layout: Layout) -> (NonNull<u8>, usize) {
+fn new_dynamically_sized_rcbox_in<A: Allocator>(
+    allocator: &A,
+    value_layout: Layout,
+) -> (NonNull<u8>, usize) {
     let (layout, rcvalue_offset) = rcbox_layout_and_value_offset(value_layout);
     unsafe {
-        let rcbox_ptr: NonNull<u8> = crate::alloc::alloc(layout);
+        let rcbox_ptr: NonNull<u8> = crate::alloc::alloc_in(allocator, layout);
 
         let refcount_ptr = rcbox_ptr.cast::<Cell<usize>>();
         refcount_ptr.as_ptr().write(Cell::new(1));
@@ -108,15 +127,29 @@ fn new_dynamically_sized_rcbox(value_layout: Layout) -> (NonNull<u8>, usize) {
 }
 
 impl<T> RcBox<WithHash<[T]>> {
-    fn new_withhash_slice(input_slice: &[T]) -> NonNull<Self>
+    fn new_withhash_slice_in<A: Allocator>(allocator: &A, input_slice: &[T]) -> NonNull<Self>
     where
         T: Copy + Hash,
+    {
+        Self::new_withhash_slice_in_with_hash(allocator, hash(input_slice), input_slice)
+    }
+
+    /// Like [`Self::new_withhash_slice_in`], but writes a caller-provided hash instead of
+    /// hashing `input_slice` itself. See [`WithHash::initialize_with_hash`] for why that
+    /// matters for `str`.
+    fn new_withhash_slice_in_with_hash<A: Allocator>(
+        allocator: &A,
+        hash_value: u64,
+        input_slice: &[T],
+    ) -> NonNull<Self>
+    where
+        T: Copy,
     {
         let (withhash_layout, slice_offset) = WithHash::slice_layout_and_value_offset(input_slice);
-        let (rcbox_ptr, rcvalue_offset) = new_dynamically_sized_rcbox(withhash_layout);
+        let (rcbox_ptr, rcvalue_offset) = new_dynamically_sized_rcbox_in(allocator, withhash_layout);
         unsafe {
             let withhash_ptr = rcbox_ptr.as_ptr().add(rcvalue_offset);
-            WithHash::initialize(withhash_ptr, slice_offset, input_slice);
+            WithHash::initialize_with_hash(withhash_ptr, slice_offset, hash_value, input_slice);
 
             // TODO: use `ptr::from_raw_parts_mut` when available (https://github.com/rust-lang/rust/issues/81513)
 
@@ -132,11 +165,66 @@ impl<T> RcBox<WithHash<[T]>> {
     }
 }
 
+impl<T: Copy + Hash, A: Allocator> Rc<WithHash<[T]>, A> {
+    pub fn from_slice_in(input_slice: &[T], allocator: A) -> Self {
+        Self {
+            ptr: RcBox::new_withhash_slice_in(&allocator, input_slice),
+            allocator,
+            phantom: PhantomData,
+        }
+    }
+}
+
 impl<T: Copy + Hash> From<&'_ [T]> for Rc<WithHash<[T]>> {
     fn from(input_slice: &'_ [T]) -> Self {
+        Self::from_slice_in(input_slice, Global)
+    }
+}
+
+impl<A: Allocator> Rc<WithHash<str>, A> {
+    pub fn from_str_in(value: &str, allocator: A) -> Self {
+        // Hash `value` itself, not `value.as_bytes()`: `str`'s `Hash` impl appends a
+        // terminator byte that `[u8]`'s `Hash` impl does not, so the two disagree.
+        let bytes_ptr: NonNull<RcBox<WithHash<[u8]>>> =
+            RcBox::new_withhash_slice_in_with_hash(&allocator, hash(value), value.as_bytes());
+
+        // The wide pointer metadata is compatible between `*RcBox<WithHash<[u8]>>`
+        // and `*RcBox<WithHash<str>>` (the length as a `usize` counting bytes)
+        let ptr = bytes_ptr.as_ptr() as *mut RcBox<WithHash<str>>;
+
         Self {
-            ptr: RcBox::new_withhash_slice(input_slice),
+            // SAFETY: points to a fully initialized allocation with the appropriate layout
+            ptr: unsafe { NonNull::new_unchecked(ptr) },
+            allocator,
             phantom: PhantomData,
         }
     }
 }
+
+impl From<&'_ str> for Rc<WithHash<str>> {
+    fn from(value: &'_ str) -> Self {
+        Self::from_str_in(value, Global)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rc;
+    use allocator_api2::alloc::Global;
+
+    #[test]
+    fn new_in_deref_and_clone() {
+        let a = Rc::new_in(42, Global);
+        let b = a.clone();
+        assert_eq!(*a, 42);
+        assert_eq!(*b, 42);
+        drop(a);
+        assert_eq!(*b, 42);
+    }
+
+    #[test]
+    fn from_str_in_preserves_contents() {
+        let rc = Rc::from_str_in("hello", Global);
+        assert_eq!(&**rc, "hello");
+    }
+}
@@ -0,0 +1,147 @@
+use crate::map::NotAHasher;
+use crate::PreHash;
+use hashbrown::hash_map::RawEntryMut;
+use hashbrown::Equivalent;
+use hashbrown::HashMap;
+
+/// Like [`PreHashMap`][crate::PreHashMap] but for values without an associated payload,
+/// deduplicating prehashed values (for example `Box<WithHash<str>>`) using their
+/// stored hashes and never letting hashbrown rehash them.
+pub struct PreHashSet<T>
+where
+    T: PreHash,
+{
+    hashbrown: HashMap<T, (), NotAHasher>,
+}
+
+impl<T> PreHashSet<T>
+where
+    T: PreHash,
+    T::Hashed: Eq,
+{
+    pub fn new() -> Self {
+        Self {
+            hashbrown: HashMap::with_hasher(NotAHasher),
+        }
+    }
+
+    fn raw_entry<Q>(&self, key: &Q) -> Option<&T>
+    where
+        Q: PreHash,
+        Q::Hashed: Equivalent<T::Hashed>,
+    {
+        let hash = PreHash::precomputed_hash(key);
+        self.hashbrown
+            .raw_entry()
+            .from_hash(hash, |candidate| {
+                PreHash::hashed_value(key).equivalent(PreHash::hashed_value(candidate))
+            })
+            .map(|(value, ())| value)
+    }
+
+    fn raw_entry_mut<Q>(&mut self, key: &Q) -> RawEntryMut<'_, T, (), NotAHasher>
+    where
+        Q: PreHash,
+        Q::Hashed: Equivalent<T::Hashed>,
+    {
+        let hash = PreHash::precomputed_hash(key);
+        self.hashbrown.raw_entry_mut().from_hash(hash, |candidate| {
+            PreHash::hashed_value(key).equivalent(PreHash::hashed_value(candidate))
+        })
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&T>
+    where
+        Q: PreHash,
+        Q::Hashed: Equivalent<T::Hashed>,
+    {
+        self.raw_entry(key)
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        Q: PreHash,
+        Q::Hashed: Equivalent<T::Hashed>,
+    {
+        self.raw_entry(key).is_some()
+    }
+
+    /// Returns `true` if `value` was not already present.
+    pub fn insert(&mut self, value: T) -> bool {
+        match self.raw_entry_mut(&value) {
+            RawEntryMut::Occupied(_) => false,
+            RawEntryMut::Vacant(entry) => {
+                let hash = PreHash::precomputed_hash(&value);
+                entry.insert_with_hasher(hash, value, (), PreHash::precomputed_hash);
+                true
+            }
+        }
+    }
+
+    /// Removes and returns the stored value equivalent to `key`, if any.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        Q: PreHash,
+        Q::Hashed: Equivalent<T::Hashed>,
+    {
+        match self.raw_entry_mut(key) {
+            RawEntryMut::Occupied(entry) => Some(entry.remove_entry().0),
+            RawEntryMut::Vacant(_) => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashbrown.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashbrown.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.hashbrown.keys()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a PreHashSet<T>
+where
+    T: PreHash,
+{
+    type Item = &'a T;
+    type IntoIter = hashbrown::hash_map::Keys<'a, T, ()>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.hashbrown.keys()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PreHashSet;
+    use crate::WithHash;
+
+    #[test]
+    fn insert_contains_remove() {
+        let mut set = PreHashSet::new();
+        assert!(set.is_empty());
+        assert!(set.insert(WithHash::from(1)));
+        assert!(!set.insert(WithHash::from(1)));
+        assert_eq!(set.len(), 1);
+        assert!(!set.is_empty());
+        assert!(set.contains(&WithHash::from(1)));
+        assert!(!set.contains(&WithHash::from(2)));
+        assert_eq!(*set.remove(&WithHash::from(1)).unwrap(), 1);
+        assert!(set.is_empty());
+        assert!(set.remove(&WithHash::from(1)).is_none());
+    }
+
+    #[test]
+    fn iter_yields_all_values() {
+        let mut set = PreHashSet::new();
+        set.insert(WithHash::from(1));
+        set.insert(WithHash::from(2));
+        let mut values: Vec<i32> = set.iter().map(|v| **v).collect();
+        values.sort_unstable();
+        assert_eq!(values, [1, 2]);
+    }
+}
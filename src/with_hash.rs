@@ -2,6 +2,19 @@ use crate::hash;
 use crate::PreHash;
 use std::alloc::Layout;
 use std::hash::Hash;
+use std::ptr::NonNull;
+#[cfg(feature = "rkyv")]
+use rkyv::out_field;
+#[cfg(feature = "rkyv")]
+use rkyv::Archive;
+#[cfg(feature = "rkyv")]
+use rkyv::Archived;
+#[cfg(feature = "rkyv")]
+use rkyv::Deserialize as RkyvDeserialize;
+#[cfg(feature = "rkyv")]
+use rkyv::Fallible;
+#[cfg(feature = "rkyv")]
+use rkyv::Serialize as RkyvSerialize;
 
 /// Stores a `T` value together with its pre-computed hash.
 ///
@@ -88,6 +101,18 @@ impl<T: ?Sized> PreHash for Box<WithHash<T>> {
     }
 }
 
+impl<T: ?Sized> PreHash for crate::Rc<WithHash<T>> {
+    type Hashed = T;
+
+    fn precomputed_hash(self_: &Self) -> u64 {
+        self_.hash
+    }
+
+    fn hashed_value(self_: &Self) -> &Self::Hashed {
+        &self_.value
+    }
+}
+
 impl<T: ?Sized> PreHash for std::rc::Rc<WithHash<T>> {
     type Hashed = T;
 
@@ -100,6 +125,18 @@ impl<T: ?Sized> PreHash for std::rc::Rc<WithHash<T>> {
     }
 }
 
+impl<T: ?Sized> PreHash for crate::Arc<WithHash<T>> {
+    type Hashed = T;
+
+    fn precomputed_hash(self_: &Self) -> u64 {
+        self_.hash
+    }
+
+    fn hashed_value(self_: &Self) -> &Self::Hashed {
+        &self_.value
+    }
+}
+
 impl<T: ?Sized> PreHash for std::sync::Arc<WithHash<T>> {
     type Hashed = T;
 
@@ -117,38 +154,73 @@ impl<T> WithHash<[T]> {
     where
         T: Copy + Hash,
     {
-        let (layout, value_offset) = Self::slice_layout(input_slice.len());
+        Self::new_raw_boxed_slice_with_hash(hash(input_slice), input_slice)
+    }
+
+    /// Like [`Self::new_raw_boxed_slice`], but writes a caller-provided hash instead of
+    /// hashing `input_slice` itself. See [`Self::initialize_with_hash`] for why that
+    /// matters for `str`.
+    fn new_raw_boxed_slice_with_hash(hash_value: u64, input_slice: &'_ [T]) -> *mut Self
+    where
+        T: Copy,
+    {
+        let (layout, value_offset) = Self::slice_layout_and_value_offset(input_slice);
         unsafe {
             // SAFETY: `layout` is not zero size since `WithHash` has a `hash: u64` field.
-            let struct_ptr: *mut u8 = crate::alloc::alloc(layout);
+            let struct_ptr: NonNull<u8> = crate::alloc::alloc(layout);
 
             // SAFETY: allocated from the appropriate layout
-            Self::initialize(struct_ptr, value_offset, input_slice);
+            Self::initialize_with_hash(struct_ptr.as_ptr(), value_offset, hash_value, input_slice);
 
-            Self::as_wide_ptr(struct_ptr, input_slice.len())
+            Self::as_wide_ptr(struct_ptr.as_ptr(), input_slice.len())
         }
     }
 
-    fn slice_layout(len: usize) -> (Layout, usize) {
+    /// Computes the `Layout` of a `WithHash<[T]>` holding `slice`'s elements,
+    /// together with the byte offset of the `value` field within that layout.
+    pub(crate) fn slice_layout_and_value_offset(slice: &[T]) -> (Layout, usize) {
         // SAFETY: must match the #[repr(C)] layout of the `WithHash` struct
         let hash_layout = Layout::new::<u64>();
-        let value_layout = Layout::array::<T>(len).expect("layout computation overflow");
+        let value_layout = Layout::array::<T>(slice.len()).expect("layout computation overflow");
         let (layout, offset) = hash_layout
             .extend(value_layout)
             .expect("layout computation overflow");
         (layout.pad_to_align(), offset)
     }
 
-    /// SAFETY: `struct_ptr` must be valid for the layout returned by `slice_layout`
-    unsafe fn initialize(struct_ptr: *mut u8, value_offset: usize, input_slice: &[T])
+    /// SAFETY: `struct_ptr` must be valid for the layout returned by
+    /// `slice_layout_and_value_offset`
+    pub(crate) unsafe fn initialize(struct_ptr: *mut u8, value_offset: usize, input_slice: &[T])
     where
         T: Copy + Hash,
+    {
+        // SAFETY: same preconditions as `initialize_with_hash`
+        unsafe {
+            Self::initialize_with_hash(struct_ptr, value_offset, hash(input_slice), input_slice)
+        }
+    }
+
+    /// Like [`Self::initialize`], but writes a caller-provided hash instead of
+    /// hashing `input_slice` itself. Used when the logical hashed value is not
+    /// `input_slice` exactly, for example `str`, whose `Hash` impl appends a
+    /// length-disambiguating terminator byte that `[u8]`'s `Hash` impl does not,
+    /// so hashing `s.as_bytes()` does not agree with [`crate::hash`]`(s)`.
+    ///
+    /// SAFETY: `struct_ptr` must be valid for the layout returned by
+    /// `slice_layout_and_value_offset`
+    pub(crate) unsafe fn initialize_with_hash(
+        struct_ptr: *mut u8,
+        value_offset: usize,
+        hash_value: u64,
+        input_slice: &[T],
+    ) where
+        T: Copy,
     {
         // The first field of the struct is at offset 0:
         let hash_ptr = struct_ptr.cast::<u64>();
         // SAFETY: pointer is valid (from a successful allocation)
         // and aligned (from `Layout` computation)
-        unsafe { hash_ptr.write(hash(input_slice)) }
+        unsafe { hash_ptr.write(hash_value) }
         // SAFETY: both pointers are within a successful allocation
         // `value_offset` is counted in bytes, so add it before casting.
         let value_ptr = unsafe { struct_ptr.add(value_offset) }.cast::<T>();
@@ -177,9 +249,67 @@ impl<T: Copy + Hash> From<&'_ [T]> for Box<WithHash<[T]>> {
     }
 }
 
+#[cfg(feature = "rkyv")]
+impl<T> WithHash<T> {
+    /// Rebuilds a `WithHash` from an already-known hash, skipping `Hash`
+    /// entirely. Used when deserializing from an archive where the hash was
+    /// stored alongside the value.
+    fn from_raw_parts(hash: u64, value: T) -> Self {
+        Self { hash, value }
+    }
+}
+
+/// Archived form of [`WithHash`]. The `hash` field is stored inline and is
+/// never recomputed: reading it back after loading an archive is a plain
+/// field load, not a call to [`crate::hash`].
+#[cfg(feature = "rkyv")]
+pub struct ArchivedWithHash<T: Archive> {
+    pub hash: u64,
+    pub value: Archived<T>,
+}
+
+#[cfg(feature = "rkyv")]
+impl<T: Archive> Archive for WithHash<T> {
+    type Archived = ArchivedWithHash<T>;
+    type Resolver = T::Resolver;
+
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        let (fp, fo) = out_field!(out.hash);
+        PreHash::precomputed_hash(self).resolve(pos + fp, (), fo);
+        let (fp, fo) = out_field!(out.value);
+        PreHash::hashed_value(self).resolve(pos + fp, resolver, fo);
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<T: Archive, S: Fallible + ?Sized> RkyvSerialize<S> for WithHash<T>
+where
+    T: RkyvSerialize<S>,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        PreHash::hashed_value(self).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<T: Archive, D: Fallible + ?Sized> RkyvDeserialize<WithHash<T>, D> for ArchivedWithHash<T>
+where
+    Archived<T>: RkyvDeserialize<T, D>,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<WithHash<T>, D::Error> {
+        Ok(WithHash::from_raw_parts(
+            self.hash,
+            self.value.deserialize(deserializer)?,
+        ))
+    }
+}
+
 impl From<&'_ str> for Box<WithHash<str>> {
     fn from(value: &'_ str) -> Self {
-        let ptr: *mut WithHash<[u8]> = WithHash::new_raw_boxed_slice(value.as_bytes());
+        // Hash `value` itself, not `value.as_bytes()`: `str`'s `Hash` impl appends a
+        // terminator byte that `[u8]`'s `Hash` impl does not, so the two disagree.
+        let ptr: *mut WithHash<[u8]> =
+            WithHash::new_raw_boxed_slice_with_hash(hash(value), value.as_bytes());
 
         // The wide pointer metadata is compatible between `*[u8]` and `*str`
         // (the length as a `usize` counting bytes)
@@ -189,3 +319,25 @@ impl From<&'_ str> for Box<WithHash<str>> {
         unsafe { Box::from_raw(ptr) }
     }
 }
+
+#[cfg(all(test, feature = "rkyv"))]
+mod rkyv_tests {
+    use super::WithHash;
+    use crate::PreHash;
+    use rkyv::Deserialize as RkyvDeserialize;
+
+    #[test]
+    fn roundtrip_preserves_value_and_hash() {
+        let original = WithHash::from(42u32);
+        let bytes = rkyv::to_bytes::<_, 256>(&original).unwrap();
+        let archived = unsafe { rkyv::archived_root::<WithHash<u32>>(&bytes) };
+        assert_eq!(archived.hash, PreHash::precomputed_hash(&original));
+        assert_eq!(archived.value, 42);
+
+        let deserialized: WithHash<u32> = archived.deserialize(&mut rkyv::Infallible).unwrap();
+        assert_eq!(
+            PreHash::precomputed_hash(&deserialized),
+            PreHash::precomputed_hash(&original)
+        );
+    }
+}